@@ -1,32 +1,73 @@
 use anyhow::{Context, Result};
 use axum::{
+    extract::{Request, State},
+    middleware::{self, Next},
     response::{IntoResponse, Response},
     routing::get,
     Router,
 };
+use axum_server::tls_rustls::RustlsConfig;
+use base64::Engine;
 use byte_unit::Byte;
-use clap::Parser;
-use http::{header, status::StatusCode};
+use clap::{Args, Parser};
+use http::{header, status::StatusCode, HeaderMap};
 use tracing_subscriber::{layer::SubscriberExt, util::SubscriberInitExt};
 use uuid::Uuid;
 
-use std::{net::SocketAddr, path::PathBuf};
+use std::{
+    collections::HashMap,
+    net::SocketAddr,
+    path::PathBuf,
+    sync::Arc,
+    time::{SystemTime, UNIX_EPOCH},
+};
 
 #[tokio::main(flavor = "current_thread")]
 async fn main() -> Result<()> {
     let cli = Cli::parse();
 
-    serve(&cli.listen).await;
+    serve(&cli).await;
     Ok(())
 }
 
+const SYSFS_BCACHEFS_ROOT: &str = "/sys/fs/bcachefs";
+
 #[derive(Parser)]
 struct Cli {
     #[arg(long, default_value = "[::1]:22903")]
     listen: SocketAddr,
+
+    /// Root of the bcachefs sysfs tree. Point this at a fixture directory to
+    /// scrape captured sysfs files instead of a live mount.
+    #[arg(long, default_value = SYSFS_BCACHEFS_ROOT)]
+    sysfs_root: PathBuf,
+
+    /// PEM-encoded certificate chain. Requires `--tls-key`; enables TLS.
+    #[arg(long, requires = "tls_key")]
+    tls_cert: Option<PathBuf>,
+    /// PEM-encoded private key. Requires `--tls-cert`.
+    #[arg(long, requires = "tls_cert")]
+    tls_key: Option<PathBuf>,
+
+    /// File containing a bearer token. When set, `/metrics` requires a matching
+    /// `Authorization: Bearer <token>` header (or HTTP basic credentials whose
+    /// password, or whole `user:password` string, is the token).
+    #[arg(long)]
+    auth_token_file: Option<PathBuf>,
+
+    #[command(flatten)]
+    collectors: CollectorFlags,
+}
+
+/// State shared with the `/metrics` handler: the configured collector registry
+/// and the context (sysfs root) every collection pass runs against.
+#[derive(Clone)]
+struct AppState {
+    registry: Arc<Registry>,
+    context: Arc<CollectContext>,
 }
 
-pub(crate) async fn serve(listen: &SocketAddr) {
+pub(crate) async fn serve(cli: &Cli) {
     tracing_subscriber::registry()
         .with(
             tracing_subscriber::EnvFilter::try_from_default_env()
@@ -35,13 +76,101 @@ pub(crate) async fn serve(listen: &SocketAddr) {
         .with(tracing_subscriber::fmt::layer())
         .init();
 
+    let state = AppState {
+        registry: Arc::new(cli.collectors.registry()),
+        context: Arc::new(CollectContext {
+            sysfs_root: cli.sysfs_root.clone(),
+        }),
+    };
+
     // build our application with some routes
-    let app = Router::new().route("/metrics", get(http_metrics));
+    let mut app = Router::new()
+        .route("/metrics", get(http_metrics))
+        .with_state(state);
+
+    // gate /metrics behind a bearer/basic token when configured
+    if let Some(path) = &cli.auth_token_file {
+        let token = std::fs::read_to_string(path)
+            .expect("reading --auth-token-file")
+            .trim()
+            .to_string();
+        app = app.layer(middleware::from_fn_with_state(Arc::new(token), require_auth));
+    }
+
+    // run it, over TLS when a certificate and key are configured
+    match (&cli.tls_cert, &cli.tls_key) {
+        (Some(cert), Some(key)) => {
+            let config = RustlsConfig::from_pem_file(cert, key).await.unwrap();
+            tracing::debug!("listening on {} (TLS)", cli.listen);
+            axum_server::bind_rustls(cli.listen, config)
+                .serve(app.into_make_service())
+                .await
+                .unwrap();
+        }
+        _ => {
+            let listener = tokio::net::TcpListener::bind(&cli.listen).await.unwrap();
+            tracing::debug!("listening on {}", listener.local_addr().unwrap());
+            axum::serve(listener, app).await.unwrap();
+        }
+    }
+}
+
+/// Middleware enforcing the configured token on every request. Accepts either
+/// `Authorization: Bearer <token>` or HTTP basic credentials whose password
+/// (or whole `user:password` string) equals the token, and returns `401`
+/// otherwise.
+async fn require_auth(State(token): State<Arc<String>>, request: Request, next: Next) -> Response {
+    if authorized(&token, &request) {
+        next.run(request).await
+    } else {
+        (
+            StatusCode::UNAUTHORIZED,
+            [(header::WWW_AUTHENTICATE, "Bearer")],
+            "unauthorized\n",
+        )
+            .into_response()
+    }
+}
+
+fn authorized(token: &str, request: &Request) -> bool {
+    let Some(header) = request
+        .headers()
+        .get(header::AUTHORIZATION)
+        .and_then(|v| v.to_str().ok())
+    else {
+        return false;
+    };
+    if let Some(bearer) = header.strip_prefix("Bearer ") {
+        return constant_time_eq(bearer, token);
+    }
+    if let Some(basic) = header.strip_prefix("Basic ") {
+        if let Ok(decoded) = base64::engine::general_purpose::STANDARD.decode(basic) {
+            if let Ok(creds) = String::from_utf8(decoded) {
+                // `user:password`; accept when the password (or the whole
+                // credential) matches the token.
+                let password = creds.split_once(':').map(|(_, p)| p).unwrap_or(&creds);
+                return constant_time_eq(password, token) || constant_time_eq(&creds, token);
+            }
+        }
+    }
+    false
+}
 
-    // run it
-    let listener = tokio::net::TcpListener::bind(listen).await.unwrap();
-    tracing::debug!("listening on {}", listener.local_addr().unwrap());
-    axum::serve(listener, app).await.unwrap();
+/// Compares two strings without leaking the position of the first mismatched
+/// byte through timing, so a client guessing the auth token one byte at a
+/// time can't use response latency as an oracle. The length check is not
+/// constant-time, but leaking the token's length is an accepted tradeoff (the
+/// `subtle` crate's `ConstantTimeEq` makes the same one).
+fn constant_time_eq(a: &str, b: &str) -> bool {
+    let (a, b) = (a.as_bytes(), b.as_bytes());
+    if a.len() != b.len() {
+        return false;
+    }
+    let mut diff = 0u8;
+    for (x, y) in a.iter().zip(b.iter()) {
+        diff |= x ^ y;
+    }
+    diff == 0
 }
 
 struct AppError(anyhow::Error);
@@ -67,35 +196,173 @@ impl IntoResponse for AppError {
     }
 }
 
-async fn http_metrics() -> std::result::Result<impl IntoResponse, AppError> {
+async fn http_metrics(
+    State(state): State<AppState>,
+    headers: HeaderMap,
+) -> std::result::Result<impl IntoResponse, AppError> {
+    let openmetrics = wants_openmetrics(&headers);
+    // Collection reads sysfs synchronously (`read_to_string`/`read_dir`) across
+    // every filesystem and device. Running that directly in the handler future
+    // would block the reactor and head-of-line-block every other scrape and
+    // liveness probe, so we move the whole pass onto the blocking pool.
+    let registry = state.registry.clone();
+    let context = state.context.clone();
+    let metrics = tokio::task::spawn_blocking(move || registry.collect(&context)).await??;
+    let body = encode_exposition(&metrics, openmetrics, SystemTime::now());
+
+    let content_type = if openmetrics {
+        "application/openmetrics-text; version=1.0.0; charset=utf-8"
+    } else {
+        "text/plain; version=0.0.4"
+    };
+    Ok(([(header::CONTENT_TYPE, content_type)], body))
+}
+
+/// Returns true if the scraper advertised the OpenMetrics content type in its
+/// `Accept` header, in which case we emit the stricter 1.0.0 exposition.
+fn wants_openmetrics(headers: &HeaderMap) -> bool {
+    headers
+        .get(header::ACCEPT)
+        .and_then(|v| v.to_str().ok())
+        .is_some_and(|accept| accept.contains("application/openmetrics-text"))
+}
+
+/// Milliseconds since the Unix epoch, the timestamp format the classic 0.0.4
+/// Prometheus exposition expects as a sample's third field.
+fn format_epoch_millis(timestamp: SystemTime) -> String {
+    timestamp
+        .duration_since(UNIX_EPOCH)
+        .unwrap_or_default()
+        .as_millis()
+        .to_string()
+}
+
+/// Seconds since the Unix epoch with millisecond precision, the timestamp
+/// format OpenMetrics expects as a sample's third field.
+fn format_openmetrics_timestamp(timestamp: SystemTime) -> String {
+    let since_epoch = timestamp.duration_since(UNIX_EPOCH).unwrap_or_default();
+    format!("{}.{:03}", since_epoch.as_secs(), since_epoch.subsec_millis())
+}
+
+/// Render a set of samples as a single Prometheus/OpenMetrics exposition.
+///
+/// Samples are grouped by metric name so that each family emits exactly one
+/// `# HELP`/`# TYPE` pair followed by all of its series; Prometheus rejects an
+/// exposition where series for the same family are interleaved with others.
+/// Name order follows first appearance so the output is deterministic. Every
+/// sample carries `timestamp`, the instant collection ran, so a scrape that
+/// sits in a queue for a while doesn't get misattributed to whenever
+/// Prometheus happened to ingest it.
+fn encode_exposition(metrics: &[Metric], openmetrics: bool, timestamp: SystemTime) -> String {
+    let mut order: Vec<&'static str> = Vec::new();
+    let mut groups: HashMap<&'static str, Vec<&Metric>> = HashMap::new();
+    for metric in metrics {
+        if !groups.contains_key(metric.name) {
+            order.push(metric.name);
+            groups.insert(metric.name, Vec::new());
+        }
+        groups.get_mut(metric.name).unwrap().push(metric);
+    }
+
     let mut out = String::new();
-    for metric in get_metrics()? {
-        out += &metric.encode();
+    for name in order {
+        let group = &groups[name];
+        let family = group[0].family_name(openmetrics);
+        out += &format!("# HELP {family} {help}\n", help = group[0].help);
+        out += &format!(
+            "# TYPE {family} {type_}\n",
+            type_ = group[0].metric_type.as_str()
+        );
+        for metric in group {
+            out += &metric.encode_sample(timestamp, openmetrics);
+        }
+    }
+    if openmetrics {
+        out += "# EOF\n";
     }
+    out
+}
 
-    Ok(([(header::CONTENT_TYPE, "text/plain; version=0.0.4")], out))
+#[derive(Clone, Copy, PartialEq, Eq)]
+enum MetricType {
+    Gauge,
+    Counter,
+}
+
+impl MetricType {
+    fn as_str(self) -> &'static str {
+        match self {
+            MetricType::Gauge => "gauge",
+            MetricType::Counter => "counter",
+        }
+    }
 }
 
 struct Metric {
     name: &'static str,
+    help: &'static str,
+    metric_type: MetricType,
     labels: Labels,
     value: f64,
 }
 type Labels = Vec<(&'static str, String)>;
 
 impl Metric {
-    fn encode(&self) -> String {
+    /// Construct a gauge sample.
+    fn gauge(name: &'static str, help: &'static str, labels: Labels, value: f64) -> Metric {
+        Metric {
+            name,
+            help,
+            metric_type: MetricType::Gauge,
+            labels,
+            value,
+        }
+    }
+
+    /// Name used on `# HELP`/`# TYPE` lines. In the classic 0.0.4 format a
+    /// counter carries the `_total` suffix everywhere, whereas OpenMetrics
+    /// keeps the bare family name on the metadata lines and only suffixes the
+    /// samples.
+    fn family_name(&self, openmetrics: bool) -> String {
+        if self.metric_type == MetricType::Counter && !openmetrics {
+            format!("{}_total", self.name)
+        } else {
+            self.name.to_string()
+        }
+    }
+
+    /// Name used on the sample line: counters always carry `_total`.
+    fn sample_name(&self) -> String {
+        if self.metric_type == MetricType::Counter {
+            format!("{}_total", self.name)
+        } else {
+            self.name.to_string()
+        }
+    }
+
+    /// Encode one sample line, including its collection timestamp: classic
+    /// 0.0.4 exposition wants milliseconds since the epoch, OpenMetrics wants
+    /// fractional seconds.
+    fn encode_sample(&self, timestamp: SystemTime, openmetrics: bool) -> String {
         let labels = Self::encode_labels(&self.labels);
+        let timestamp = if openmetrics {
+            format_openmetrics_timestamp(timestamp)
+        } else {
+            format_epoch_millis(timestamp)
+        };
         format!(
-            "{name}{{{labels}}} {value}\n",
-            name = self.name,
+            "{name}{{{labels}}} {value} {timestamp}\n",
+            name = self.sample_name(),
             value = self.value
         )
     }
 
     fn encode_labels(labels: &[(&'static str, String)]) -> String {
         let mut out = String::new();
-        for (key, value) in labels {
+        for (i, (key, value)) in labels.iter().enumerate() {
+            if i > 0 {
+                out.push(',');
+            }
             out.push_str(key);
             out.push_str("=\"");
             out.push_str(
@@ -104,41 +371,365 @@ impl Metric {
                     .replace('\n', r#"\n"#)
                     .replace('"', r#"\""#),
             );
-            out.push_str("\",");
+            out.push('"');
         }
         out
     }
 }
 
-fn get_metrics() -> Result<Vec<Metric>> {
-    let mut metrics = Vec::new();
-    for fs in find_bcachefs()? {
-        metrics.append(&mut fs.get_metrics()?);
+/// Everything a collector needs to know about where to read from. Today that
+/// is just the sysfs root; keeping it in a struct lets new collectors take
+/// further configuration without changing every `collect` signature.
+struct CollectContext {
+    sysfs_root: PathBuf,
+}
+
+impl CollectContext {
+    fn find_bcachefs(&self) -> Result<Vec<Fs>> {
+        let mut fs = Vec::new();
+        for entry in self.sysfs_root.read_dir()? {
+            fs.push(Fs {
+                uuid: Uuid::parse_str(entry?.file_name().to_str().unwrap())?,
+                sysfs_root: self.sysfs_root.clone(),
+            });
+        }
+        Ok(fs)
     }
-    Ok(metrics)
 }
 
-const SYSFS_BCACHEFS_ROOT: &str = "/sys/fs/bcachefs";
-fn find_bcachefs() -> Result<Vec<Fs>> {
-    let mut fs = Vec::new();
-    for entry in PathBuf::from(SYSFS_BCACHEFS_ROOT).read_dir()? {
-        fs.push(Fs(Uuid::parse_str(entry?.file_name().to_str().unwrap())?));
+/// A self-contained source of metrics. Each collector owns the sysfs files it
+/// reads, so adding a new metric source is a matter of implementing this trait
+/// and registering it rather than threading fields through `Fs`/`Device`.
+trait Collector {
+    /// Stable short name used for the `--collector.<name>` flags.
+    fn name(&self) -> &'static str;
+    fn collect(&self, ctx: &CollectContext) -> Result<Vec<Metric>>;
+}
+
+/// The set of enabled collectors, iterated on every scrape.
+struct Registry {
+    collectors: Vec<Box<dyn Collector + Send + Sync>>,
+}
+
+impl Registry {
+    fn collect(&self, ctx: &CollectContext) -> Result<Vec<Metric>> {
+        let mut metrics = Vec::new();
+        for collector in &self.collectors {
+            metrics.append(
+                &mut collector
+                    .collect(ctx)
+                    .with_context(|| format!("collector {}", collector.name()))?,
+            );
+        }
+        Ok(metrics)
+    }
+}
+
+/// node_exporter-style collector toggles. For each collector `--collector.<n>`
+/// force-enables it and `--no-collector.<n>` disables it; when neither is given
+/// the collector's built-in default applies.
+#[derive(Args)]
+struct CollectorFlags {
+    #[arg(long = "collector.alloc")]
+    collector_alloc: bool,
+    #[arg(long = "no-collector.alloc")]
+    no_collector_alloc: bool,
+    #[arg(long = "collector.counters")]
+    collector_counters: bool,
+    #[arg(long = "no-collector.counters")]
+    no_collector_counters: bool,
+    #[arg(long = "collector.fs")]
+    collector_fs: bool,
+    #[arg(long = "no-collector.fs")]
+    no_collector_fs: bool,
+}
+
+/// Resolve a node_exporter-style on/off pair against a default.
+fn collector_enabled(default: bool, on: bool, off: bool) -> bool {
+    on || (default && !off)
+}
+
+impl CollectorFlags {
+    fn registry(&self) -> Registry {
+        let mut collectors: Vec<Box<dyn Collector + Send + Sync>> = Vec::new();
+        if collector_enabled(true, self.collector_alloc, self.no_collector_alloc) {
+            collectors.push(Box::new(AllocCollector));
+        }
+        if collector_enabled(true, self.collector_counters, self.no_collector_counters) {
+            collectors.push(Box::new(CountersCollector));
+        }
+        if collector_enabled(true, self.collector_fs, self.no_collector_fs) {
+            collectors.push(Box::new(FsCollector));
+        }
+        Registry { collectors }
+    }
+}
+
+/// The original per-device allocation and capacity metrics, read from each
+/// device's `alloc_debug` file.
+struct AllocCollector;
+impl Collector for AllocCollector {
+    fn name(&self) -> &'static str {
+        "alloc"
+    }
+
+    fn collect(&self, ctx: &CollectContext) -> Result<Vec<Metric>> {
+        let mut metrics = Vec::new();
+        for fs in ctx.find_bcachefs()? {
+            for device in fs.find_devices()? {
+                metrics.append(&mut device.alloc_debug(&device.device_labels()?)?);
+            }
+        }
+        Ok(metrics)
+    }
+}
+
+/// Cumulative event counters from `/sys/fs/bcachefs/<uuid>/counters/`. Every
+/// file there is a monotonic counter (io_read, bucket_alloc, move_extent, …),
+/// so they are exported as `counter`-typed series on which `rate()` is valid.
+struct CountersCollector;
+impl Collector for CountersCollector {
+    fn name(&self) -> &'static str {
+        "counters"
+    }
+
+    fn collect(&self, ctx: &CollectContext) -> Result<Vec<Metric>> {
+        let mut metrics = Vec::new();
+        for fs in ctx.find_bcachefs()? {
+            let dir = fs.path().join("counters");
+            if !dir.is_dir() {
+                continue;
+            }
+            for entry in dir.read_dir()? {
+                let entry = entry?;
+                let counter = entry.file_name().to_str().unwrap().to_string();
+                let content = std::fs::read_to_string(entry.path())
+                    .with_context(|| format!("reading counters/{counter}"))?;
+                // Most files report a "since mount"/"since filesystem
+                // creation" pair, one `descriptor value` line each; some
+                // report a single bare value with no descriptor. Emit one
+                // series per line either way, tagging the paired form with
+                // the `since` epoch it was measured from.
+                for line in content.lines() {
+                    let line = line.trim();
+                    if line.is_empty() {
+                        continue;
+                    }
+                    let mut labels = fs.fs_labels();
+                    labels.push(("counter", counter.clone()));
+                    let value = match line.rsplit_once(char::is_whitespace) {
+                        Some((descriptor, value)) => {
+                            let descriptor = descriptor.trim().trim_end_matches(':').trim();
+                            let since = descriptor
+                                .strip_prefix("since ")
+                                .unwrap_or(descriptor)
+                                .replace(' ', "_");
+                            labels.push(("since", since));
+                            value
+                                .trim()
+                                .parse()
+                                .with_context(|| format!("counters/{counter}: value={value:?}"))?
+                        }
+                        None => line
+                            .parse()
+                            .with_context(|| format!("counters/{counter}: value={line:?}"))?,
+                    };
+                    metrics.push(Metric {
+                        name: "bcachefs_counter",
+                        help: "Cumulative bcachefs event counter.",
+                        metric_type: MetricType::Counter,
+                        labels,
+                        value,
+                    });
+                }
+            }
+        }
+        Ok(metrics)
+    }
+}
+
+/// Filesystem-wide metrics read from the top-level sysfs files under
+/// `/sys/fs/bcachefs/<uuid>/`, as opposed to the per-device files the alloc
+/// collector walks. Every series carries the `fs` UUID and, when the mount has
+/// one, its human-readable `label`.
+struct FsCollector;
+impl Collector for FsCollector {
+    fn name(&self) -> &'static str {
+        "fs"
+    }
+
+    fn collect(&self, ctx: &CollectContext) -> Result<Vec<Metric>> {
+        let mut metrics = Vec::new();
+        for fs in ctx.find_bcachefs()? {
+            let mut labels = fs.fs_labels();
+            labels.push(("label", fs.mount_label()?.unwrap_or_default()));
+            metrics.append(&mut fs.usage_metrics(&labels)?);
+            metrics.append(&mut fs.compression_metrics(&labels)?);
+            metrics.append(&mut fs.journal_metrics(&labels)?);
+        }
+        Ok(metrics)
     }
-    Ok(fs)
 }
 
 #[derive(Debug)]
-struct Fs(Uuid);
+struct Fs {
+    uuid: Uuid,
+    sysfs_root: PathBuf,
+}
 impl Fs {
-    fn get_metrics(&self) -> Result<Vec<Metric>> {
+    fn path(&self) -> PathBuf {
+        self.sysfs_root.join(self.uuid.to_string())
+    }
+
+    /// The `fs` UUID label shared by every filesystem-scoped series.
+    fn fs_labels(&self) -> Labels {
+        vec![("fs", self.uuid.to_string())]
+    }
+
+    /// The operator-assigned filesystem label, if the mount has one.
+    fn mount_label(&self) -> Result<Option<String>> {
+        match std::fs::read_to_string(self.path().join("label")) {
+            Ok(label) => Ok(Some(label.trim().to_string())),
+            Err(e) if e.kind() == std::io::ErrorKind::NotFound => Ok(None),
+            Err(e) => Err(e).with_context(|| "reading $fs/label"),
+        }
+    }
+
+    /// Total/used/free capacity and per-replication-level usage from the
+    /// top-level `usage` file. Values are sector counts. Each line is
+    /// `key: value`, the same colon-glued-to-the-key convention
+    /// `journal_metrics` below assumes for `journal_debug`; `replicas` lines
+    /// carry an extra `level` token before the sector count
+    /// (`replicas: <level> <sectors>`). Unrecognised lines are ignored so a
+    /// newer kernel adding fields does not break the scrape.
+    fn usage_metrics(&self, labels: &Labels) -> Result<Vec<Metric>> {
+        let Some(content) = read_optional(&self.path().join("usage"))? else {
+            return Ok(Vec::new());
+        };
         let mut metrics = Vec::new();
-        for device in self.find_devices()? {
-            metrics.append(&mut device.get_metrics()?);
+        for line in content.lines() {
+            let Some((key, rest)) = line.split_once(':') else {
+                continue;
+            };
+            let rest = rest.trim();
+            match key.trim() {
+                "capacity" => metrics.push(Metric::gauge(
+                    "bcachefs_fs_capacity_bytes",
+                    "Total filesystem capacity in bytes.",
+                    labels.clone(),
+                    sectors_to_bytes(rest)?,
+                )),
+                "used" => metrics.push(Metric::gauge(
+                    "bcachefs_fs_used_bytes",
+                    "Used filesystem space in bytes.",
+                    labels.clone(),
+                    sectors_to_bytes(rest)?,
+                )),
+                "free" => metrics.push(Metric::gauge(
+                    "bcachefs_fs_free_bytes",
+                    "Free filesystem space in bytes.",
+                    labels.clone(),
+                    sectors_to_bytes(rest)?,
+                )),
+                "replicas" => {
+                    let Some((level, sectors)) = rest.split_once(char::is_whitespace) else {
+                        continue;
+                    };
+                    let mut labels = labels.clone();
+                    labels.push(("replicas", level.trim().to_string()));
+                    metrics.push(Metric::gauge(
+                        "bcachefs_fs_replicas_bytes",
+                        "Filesystem space used at a given replication level, in bytes.",
+                        labels,
+                        sectors_to_bytes(sectors.trim())?,
+                    ));
+                }
+                _ => continue,
+            }
         }
         Ok(metrics)
     }
-    fn path(&self) -> PathBuf {
-        PathBuf::from(SYSFS_BCACHEFS_ROOT).join(self.0.to_string())
+
+    /// Compression ratio and compressed/uncompressed byte totals from
+    /// `compression_stats`. Each data row is `type compressed uncompressed …`
+    /// with human-readable byte quantities (e.g. `1.5 GiB`); totals are summed
+    /// across all compression types.
+    fn compression_metrics(&self, labels: &Labels) -> Result<Vec<Metric>> {
+        let Some(content) = read_optional(&self.path().join("compression_stats"))? else {
+            return Ok(Vec::new());
+        };
+        let mut compressed = 0.0;
+        let mut uncompressed = 0.0;
+        for line in content.lines().skip(1) {
+            // After the type name, the remaining tokens are `<number> <unit>`
+            // byte quantities; the first two are compressed then uncompressed.
+            let cells: Vec<_> = line.split_whitespace().collect();
+            if let [_type, cnum, cunit, unum, uunit, ..] = cells[..] {
+                compressed += parse_byte(&format!("{cnum} {cunit}"))?;
+                uncompressed += parse_byte(&format!("{unum} {uunit}"))?;
+            }
+        }
+        let mut metrics = vec![
+            Metric::gauge(
+                "bcachefs_fs_compressed_bytes",
+                "Bytes stored after compression.",
+                labels.clone(),
+                compressed,
+            ),
+            Metric::gauge(
+                "bcachefs_fs_uncompressed_bytes",
+                "Logical bytes before compression.",
+                labels.clone(),
+                uncompressed,
+            ),
+        ];
+        if compressed > 0.0 {
+            metrics.push(Metric::gauge(
+                "bcachefs_fs_compression_ratio",
+                "Uncompressed bytes divided by compressed bytes.",
+                labels.clone(),
+                uncompressed / compressed,
+            ));
+        }
+        Ok(metrics)
+    }
+
+    /// Journal sequence state from `journal_debug`. Only the well-known integer
+    /// fields are exported; the file carries much more debug text.
+    fn journal_metrics(&self, labels: &Labels) -> Result<Vec<Metric>> {
+        let Some(content) = read_optional(&self.path().join("journal_debug"))? else {
+            return Ok(Vec::new());
+        };
+        // `cur_seq` and `seq` are two names bcachefs has used over time for the
+        // same field; collect by target metric name rather than pushing every
+        // match, so if a `journal_debug` ever carries both keys at once we
+        // still emit one sample per series instead of a duplicate.
+        let mut values: HashMap<&'static str, f64> = HashMap::new();
+        for line in content.lines() {
+            let Some((key, value)) = line.split_once(':') else {
+                continue;
+            };
+            let Ok(value) = value.trim().parse::<f64>() else {
+                continue;
+            };
+            let name = match key.trim() {
+                "cur_seq" | "seq" => "bcachefs_fs_journal_cur_seq",
+                "last_seq_ondisk" => "bcachefs_fs_journal_last_seq_ondisk",
+                _ => continue,
+            };
+            values.insert(name, value);
+        }
+        Ok(values
+            .into_iter()
+            .map(|(name, value)| {
+                Metric::gauge(
+                    name,
+                    "bcachefs journal sequence number.",
+                    labels.clone(),
+                    value,
+                )
+            })
+            .collect())
     }
 
     fn find_devices(&self) -> Result<Vec<Device>> {
@@ -169,8 +760,9 @@ struct Device<'a> {
     device_no: usize,
 }
 impl Device<'_> {
-    fn get_metrics(&self) -> Result<Vec<Metric>> {
-        let mut metrics = Vec::new();
+    /// The `fs`/`device_no`/`device`/`label` labels shared by every per-device
+    /// series, read once per device and cloned onto each sample.
+    fn device_labels(&self) -> Result<Labels> {
         let device_name = self
             .path()
             .join("block")
@@ -184,14 +776,12 @@ impl Device<'_> {
             .with_context(|| "reading dev-$x/label")?
             .trim()
             .to_string();
-        let device_labels = vec![
-            ("fs", self.fs.0.to_string()),
+        Ok(vec![
+            ("fs", self.fs.uuid.to_string()),
             ("device_no", self.device_no.to_string()),
             ("device", device_name),
             ("label", label),
-        ];
-        metrics.append(&mut self.alloc_debug(&device_labels)?);
-        Ok(metrics)
+        ])
     }
 
     fn path(&self) -> PathBuf {
@@ -215,6 +805,8 @@ impl Device<'_> {
                     labels.push(("type", type_.to_string()));
                     metrics.push(Metric {
                         name: "bcachefs_dev_alloc_bytes",
+                        help: "Allocated bytes on a device by allocation type.",
+                        metric_type: MetricType::Gauge,
                         labels,
                         value: sectors_to_bytes(sectors)?,
                     });
@@ -222,6 +814,8 @@ impl Device<'_> {
                 ["capacity", buckets] => {
                     metrics.push(Metric {
                         name: "bcachefs_dev_capacity",
+                        help: "Total capacity of a device in bytes.",
+                        metric_type: MetricType::Gauge,
                         labels: device_labels.clone(),
                         value: self.buckets_to_bytes(buckets)?,
                     });
@@ -250,10 +844,232 @@ impl Device<'_> {
     }
 }
 
+/// Read a sysfs file that may legitimately be absent (e.g. a feature not
+/// enabled on this filesystem), returning `None` instead of an error when it
+/// does not exist.
+fn read_optional(path: &std::path::Path) -> Result<Option<String>> {
+    match std::fs::read_to_string(path) {
+        Ok(content) => Ok(Some(content)),
+        Err(e) if e.kind() == std::io::ErrorKind::NotFound => Ok(None),
+        Err(e) => Err(e).with_context(|| format!("reading {}", path.display())),
+    }
+}
+
+/// Parse a human-readable byte quantity such as `1.5 GiB` into bytes.
+fn parse_byte(s: &str) -> Result<f64> {
+    Ok(Byte::parse_str(s, true)
+        .with_context(|| format!("byte quantity={s:?}"))?
+        .as_u64() as f64)
+}
+
+/// Shared sector-to-byte conversion used by the alloc and fs collectors.
+///
+/// Apparently the sectors are always 1<<9 = 512 bytes. Even when the disk runs with 4k sectors.
+fn sector_count_to_bytes(sectors: u64) -> f64 {
+    (sectors << 9) as f64
+}
+
 fn sectors_to_bytes(sectors: &str) -> Result<f64> {
-    // Apparently the sectors are always 2<<9 = 512 bytes. Even when the disk runs with 4k sectors.
-    Ok((sectors
-        .parse::<usize>()
-        .with_context(|| format!("sectors={sectors:?}"))?
-        << 9) as f64)
+    Ok(sector_count_to_bytes(
+        sectors
+            .parse::<u64>()
+            .with_context(|| format!("sectors={sectors:?}"))?,
+    ))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::sync::atomic::{AtomicU64, Ordering};
+
+    #[test]
+    fn encode_sample_includes_timestamp_in_each_format() {
+        let metric = Metric::gauge("bcachefs_fs_used_bytes", "help text", Vec::new(), 42.0);
+        let timestamp = UNIX_EPOCH + std::time::Duration::from_millis(1_700_000_000_123);
+
+        let classic = metric.encode_sample(timestamp, false);
+        assert_eq!(classic, "bcachefs_fs_used_bytes{} 42 1700000000123\n");
+
+        let openmetrics = metric.encode_sample(timestamp, true);
+        assert_eq!(openmetrics, "bcachefs_fs_used_bytes{} 42 1700000000.123\n");
+    }
+
+    /// Builds a fresh scratch directory to stand in for `/sys/fs/bcachefs`,
+    /// so collectors can be driven against captured-looking sysfs files
+    /// instead of requiring a live mount.
+    fn fixture_root() -> PathBuf {
+        static NEXT: AtomicU64 = AtomicU64::new(0);
+        let id = NEXT.fetch_add(1, Ordering::Relaxed);
+        let root = std::env::temp_dir().join(format!(
+            "bcachefs-exporter-test-{}-{id}",
+            std::process::id()
+        ));
+        std::fs::create_dir_all(&root).unwrap();
+        root
+    }
+
+    fn write_fixture_file(fs_dir: &std::path::Path, relative: &str, content: &str) {
+        let path = fs_dir.join(relative);
+        std::fs::create_dir_all(path.parent().unwrap()).unwrap();
+        std::fs::write(path, content).unwrap();
+    }
+
+    fn label(metric: &Metric, key: &str) -> Option<String> {
+        metric
+            .labels
+            .iter()
+            .find(|(k, _)| *k == key)
+            .map(|(_, v)| v.clone())
+    }
+
+    #[test]
+    fn counters_collector_emits_bare_and_paired_values() {
+        let root = fixture_root();
+        let fs_dir = root.join(Uuid::nil().to_string());
+        write_fixture_file(
+            &fs_dir,
+            "counters/io_read",
+            "since mount 1234\nsince filesystem creation 5678\n",
+        );
+        write_fixture_file(&fs_dir, "counters/bucket_alloc", "42\n");
+
+        let ctx = CollectContext { sysfs_root: root.clone() };
+        let metrics = CountersCollector.collect(&ctx).unwrap();
+        std::fs::remove_dir_all(&root).unwrap();
+
+        assert_eq!(metrics.len(), 3);
+        assert!(metrics.iter().all(|m| m.name == "bcachefs_counter"));
+
+        let bare = metrics
+            .iter()
+            .find(|m| label(m, "counter").as_deref() == Some("bucket_alloc"))
+            .expect("bare-value counter file should still emit a series");
+        assert_eq!(bare.value, 42.0);
+        assert_eq!(label(bare, "since"), None);
+
+        let mut since_labels: Vec<_> = metrics
+            .iter()
+            .filter(|m| label(m, "counter").as_deref() == Some("io_read"))
+            .map(|m| label(m, "since"))
+            .collect();
+        since_labels.sort();
+        assert_eq!(
+            since_labels,
+            vec![
+                Some("filesystem_creation".to_string()),
+                Some("mount".to_string()),
+            ]
+        );
+    }
+
+    #[test]
+    fn registry_only_runs_collectors_that_were_registered() {
+        let root = fixture_root();
+        let fs_dir = root.join(Uuid::nil().to_string());
+        write_fixture_file(&fs_dir, "counters/io_write", "100\n");
+        write_fixture_file(&fs_dir, "usage", "capacity: 1000\nused: 400\nfree: 600\n");
+
+        let ctx = CollectContext { sysfs_root: root.clone() };
+        let registry = Registry {
+            collectors: vec![Box::new(CountersCollector)],
+        };
+        let metrics = registry.collect(&ctx).unwrap();
+        std::fs::remove_dir_all(&root).unwrap();
+
+        assert_eq!(metrics.len(), 1);
+        assert_eq!(metrics[0].name, "bcachefs_counter");
+    }
+
+    #[test]
+    fn usage_metrics_parses_capacity_used_free_and_replicas() {
+        let root = fixture_root();
+        let uuid = Uuid::nil();
+        let fs_dir = root.join(uuid.to_string());
+        write_fixture_file(
+            &fs_dir,
+            "usage",
+            "capacity: 1000\nused: 400\nfree: 600\nreplicas: 1 300\nreplicas: 2 100\n",
+        );
+
+        let fs = Fs { uuid, sysfs_root: root.clone() };
+        let labels = fs.fs_labels();
+        let metrics = fs.usage_metrics(&labels).unwrap();
+        std::fs::remove_dir_all(&root).unwrap();
+
+        assert_eq!(metrics.len(), 5);
+        let value = |name| metrics.iter().find(|m| m.name == name).unwrap().value;
+        assert_eq!(value("bcachefs_fs_capacity_bytes"), sector_count_to_bytes(1000));
+        assert_eq!(value("bcachefs_fs_used_bytes"), sector_count_to_bytes(400));
+        assert_eq!(value("bcachefs_fs_free_bytes"), sector_count_to_bytes(600));
+
+        let replicas: Vec<_> = metrics
+            .iter()
+            .filter(|m| m.name == "bcachefs_fs_replicas_bytes")
+            .collect();
+        assert_eq!(replicas.len(), 2);
+        assert!(replicas.iter().any(|m| label(m, "replicas").as_deref()
+            == Some("1")
+            && m.value == sector_count_to_bytes(300)));
+        assert!(replicas.iter().any(|m| label(m, "replicas").as_deref()
+            == Some("2")
+            && m.value == sector_count_to_bytes(100)));
+    }
+
+    #[test]
+    fn compression_metrics_sums_compressed_and_uncompressed_bytes() {
+        let root = fixture_root();
+        let uuid = Uuid::nil();
+        let fs_dir = root.join(uuid.to_string());
+        write_fixture_file(
+            &fs_dir,
+            "compression_stats",
+            "type compressed uncompressed\nzstd 1 KiB 2 KiB\nlz4 1 KiB 2 KiB\n",
+        );
+
+        let fs = Fs { uuid, sysfs_root: root.clone() };
+        let labels = fs.fs_labels();
+        let metrics = fs.compression_metrics(&labels).unwrap();
+        std::fs::remove_dir_all(&root).unwrap();
+
+        let value = |name| metrics.iter().find(|m| m.name == name).unwrap().value;
+        assert_eq!(value("bcachefs_fs_compressed_bytes"), 2048.0);
+        assert_eq!(value("bcachefs_fs_uncompressed_bytes"), 4096.0);
+        assert_eq!(value("bcachefs_fs_compression_ratio"), 2.0);
+    }
+
+    #[test]
+    fn journal_metrics_dedupes_cur_seq_and_seq() {
+        let root = fixture_root();
+        let uuid = Uuid::nil();
+        let fs_dir = root.join(uuid.to_string());
+        write_fixture_file(
+            &fs_dir,
+            "journal_debug",
+            "cur_seq: 42\nseq: 42\nlast_seq_ondisk: 40\nirrelevant: garbage\n",
+        );
+
+        let fs = Fs { uuid, sysfs_root: root.clone() };
+        let labels = fs.fs_labels();
+        let metrics = fs.journal_metrics(&labels).unwrap();
+        std::fs::remove_dir_all(&root).unwrap();
+
+        let cur_seq: Vec<_> = metrics
+            .iter()
+            .filter(|m| m.name == "bcachefs_fs_journal_cur_seq")
+            .collect();
+        assert_eq!(
+            cur_seq.len(),
+            1,
+            "cur_seq and seq must not emit duplicate series"
+        );
+        assert_eq!(cur_seq[0].value, 42.0);
+        assert_eq!(
+            metrics
+                .iter()
+                .find(|m| m.name == "bcachefs_fs_journal_last_seq_ondisk")
+                .unwrap()
+                .value,
+            40.0
+        );
+    }
 }